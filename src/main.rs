@@ -1,10 +1,38 @@
 use anyhow::Result;
+use std::collections::BTreeMap;
 use std::fs;
 use std::io;
+use std::io::BufRead;
 use std::io::Write;
-use std::process;
 
-#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+/// A parsed JSON value.
+///
+/// The variants mirror the data model used by `rustc-serialize`: integers are
+/// kept separate from floats so that whole numbers survive a round trip, and
+/// objects preserve a sorted key order by using a `BTreeMap`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Json {
+    Null,
+    Boolean(bool),
+    I64(i64),
+    U64(u64),
+    F64(f64),
+    String(String),
+    Array(Vec<Json>),
+    Object(BTreeMap<String, Json>),
+}
+
+/// A lexed JSON number. Whole numbers are kept as integers (`u64` only above
+/// `i64::MAX`); the parser falls back to `f64` when a fraction or exponent is
+/// present or the whole number fits neither integer type.
+#[derive(Debug, PartialEq, Copy, Clone)]
+enum JsonNumber {
+    Integer(i64),
+    Unsigned(u64),
+    Float(f64),
+}
+
+#[derive(Debug, PartialEq, Clone)]
 enum JsonTokens<'a> {
     OpenCurlyBrace,
     ClosingCurlyBrace,
@@ -13,31 +41,144 @@ enum JsonTokens<'a> {
     Colon,
     Comma,
     Identifier(&'a str, usize),
-    String(&'a str, usize),
+    String(String),
     Boolean(bool),
-    //TODO: Integers, Doubles !
+    Number(JsonNumber),
     Null,
     Eof,
 }
 
-fn scan_string<'a>(content: &'a str, start: usize) -> Result<(JsonTokens, usize)> {
+impl JsonTokens<'_> {
+    /// A short, human readable description of a token used when building the
+    /// `found ...` half of a parse error message.
+    fn describe(&self) -> String {
+        match self {
+            JsonTokens::OpenCurlyBrace => "'{'".to_string(),
+            JsonTokens::ClosingCurlyBrace => "'}'".to_string(),
+            JsonTokens::OpenSquareBrace => "'['".to_string(),
+            JsonTokens::ClosingSquareBrace => "']'".to_string(),
+            JsonTokens::Colon => "':'".to_string(),
+            JsonTokens::Comma => "','".to_string(),
+            JsonTokens::Identifier(value, _) => format!("identifier `{}`", value),
+            JsonTokens::String(_) => "a string".to_string(),
+            JsonTokens::Boolean(value) => format!("`{}`", value),
+            JsonTokens::Number(_) => "a number".to_string(),
+            JsonTokens::Null => "`null`".to_string(),
+            JsonTokens::Eof => "end of input".to_string(),
+        }
+    }
+}
+
+/// The source location of a token, tracked so parse errors can point at the
+/// offending byte instead of just reporting "invalid".
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Position {
+    offset: usize,
+    line: usize,
+    column: usize,
+}
+
+/// A lexed token together with the position it started at in the source.
+#[derive(Debug, Clone, PartialEq)]
+struct Token<'a> {
+    kind: JsonTokens<'a>,
+    position: Position,
+}
+
+fn scan_hex4(content: &str, start: usize, position: Position) -> Result<u16> {
+    let bytes = content.as_bytes();
+    let mut value: u16 = 0;
+    for offset in 0..4 {
+        let digit = match bytes.get(start + offset) {
+            Some(byte) => (*byte as char)
+                .to_digit(16)
+                .ok_or(ParserError::InvalidValue(position))?,
+            None => return Err(ParserError::InvalidValue(position).into()),
+        };
+        value = value * 16 + digit as u16;
+    }
+
+    Ok(value)
+}
+
+fn scan_string<'a>(
+    content: &'a str,
+    start: usize,
+    position: Position,
+) -> Result<(JsonTokens<'a>, usize)> {
+    let bytes = content.as_bytes();
+    let mut decoded = String::new();
+    //Start right after the opening quote.
     let mut end = start + 1;
-    let bytes = &mut content.as_bytes();
-    while end < bytes.len() {
+
+    loop {
         match bytes.get(end) {
-            Some(b'"') => break,
-            Some(_) => {
+            None => return Err(ParserError::MissingSymbol(position).into()),
+            Some(b'"') => {
+                end += 1;
+                break;
+            }
+            Some(b'\\') => {
+                end += 1;
+                match bytes.get(end) {
+                    Some(b'"') => decoded.push('"'),
+                    Some(b'\\') => decoded.push('\\'),
+                    Some(b'/') => decoded.push('/'),
+                    Some(b'b') => decoded.push('\u{0008}'),
+                    Some(b'f') => decoded.push('\u{000C}'),
+                    Some(b'n') => decoded.push('\n'),
+                    Some(b'r') => decoded.push('\r'),
+                    Some(b't') => decoded.push('\t'),
+                    Some(b'u') => {
+                        let high = scan_hex4(content, end + 1, position)?;
+                        end += 4;
+                        if (0xD800..=0xDBFF).contains(&high) {
+                            //A high surrogate is only valid when paired with a
+                            //following `\u` low surrogate; combine the two into
+                            //a single scalar value.
+                            if bytes.get(end + 1) != Some(&b'\\')
+                                || bytes.get(end + 2) != Some(&b'u')
+                            {
+                                return Err(ParserError::InvalidValue(position).into());
+                            }
+                            let low = scan_hex4(content, end + 3, position)?;
+                            end += 6;
+                            if !(0xDC00..=0xDFFF).contains(&low) {
+                                return Err(ParserError::InvalidValue(position).into());
+                            }
+                            let scalar = 0x10000
+                                + ((high as u32 - 0xD800) << 10)
+                                + (low as u32 - 0xDC00);
+                            decoded.push(
+                                char::from_u32(scalar).ok_or(ParserError::InvalidValue(position))?,
+                            );
+                        } else {
+                            decoded.push(
+                                char::from_u32(high as u32)
+                                    .ok_or(ParserError::InvalidValue(position))?,
+                            );
+                        }
+                    }
+                    _ => return Err(ParserError::InvalidValue(position).into()),
+                }
                 end += 1;
             }
-            None => break,
+            //Unescaped control characters are not allowed inside a string.
+            Some(byte) if *byte < 0x20 => {
+                return Err(ParserError::InvalidValue(position).into())
+            }
+            Some(_) => {
+                let ch = content[end..].chars().next().unwrap();
+                decoded.push(ch);
+                end += ch.len_utf8();
+            }
         }
     }
 
-    let length = end.saturating_sub(start);
-    Ok((JsonTokens::String(&content[start..end], length), length + 2))
+    Ok((JsonTokens::String(decoded), end - start))
 }
 
-fn scan_identifier<'a>(content: &'a str, start: usize) -> Result<(JsonTokens, usize)> {
+fn scan_identifier<'a>(content: &'a str, start: usize) -> Result<(JsonTokens<'a>, usize)> {
     let mut end = start;
     let bytes = &mut content.as_bytes();
     while end < bytes.len() {
@@ -54,81 +195,254 @@ fn scan_identifier<'a>(content: &'a str, start: usize) -> Result<(JsonTokens, us
         "true" => JsonTokens::Boolean(true),
         "false" => JsonTokens::Boolean(false),
         "null" => JsonTokens::Null,
-        //TODO: If none of the above matches we should just throw up !
         _ => JsonTokens::Identifier(&content[start..end], length)
     };
 
     Ok((token, length))
 }
 
-fn scan_json(content: &str) -> Result<Vec<JsonTokens>> {
-    let mut tokens = vec![];
-    let bytes = &mut content.as_bytes();
-    let mut current = 0;
+fn scan_number(content: &str, start: usize, position: Position) -> Result<(JsonTokens<'_>, usize)> {
+    let bytes = content.as_bytes();
+    let mut end = start;
+    let mut is_float = false;
 
-    while current < bytes.len() {
-        if bytes[current].is_ascii_whitespace() {
-            current += 1;
-            continue;
-        }
+    if bytes.get(end) == Some(&b'-') {
+        end += 1;
+    }
 
-        match bytes[current] {
-            b'{' => tokens.push(JsonTokens::OpenCurlyBrace),
-            b'}' => tokens.push(JsonTokens::ClosingCurlyBrace),
-            b'[' => tokens.push(JsonTokens::OpenSquareBrace),
-            b']' => tokens.push(JsonTokens::ClosingSquareBrace),
-            b':' => tokens.push(JsonTokens::Colon),
-            b',' => tokens.push(JsonTokens::Comma),
-            b'"' => {
-                let (token, length) = scan_string(&content[current + 1..], 0)?;
-                tokens.push(token);
-                current += length;
-                continue;
+    match bytes.get(end) {
+        //A leading zero may not be followed by more integer digits, so we stop
+        //right after it and let the grammar reject things like `01`.
+        Some(b'0') => end += 1,
+        Some(b'1'..=b'9') => {
+            end += 1;
+            while matches!(bytes.get(end), Some(b'0'..=b'9')) {
+                end += 1;
             }
-            b'_' | b'a'..=b'z' | b'A'..=b'Z' => {
-                let (token, length) = scan_identifier(&content, current)?;
+        }
+        _ => return Err(ParserError::InvalidValue(position).into()),
+    }
 
-                tokens.push(token);
-                //TODO: Refactor this into a Lexer struct which keeps the internal
-                //state globally available for all consumers so we don't need to
-                //take the consumed length from a level above us into account.
-                current += length;
+    if bytes.get(end) == Some(&b'.') {
+        is_float = true;
+        end += 1;
+        if !matches!(bytes.get(end), Some(b'0'..=b'9')) {
+            return Err(ParserError::InvalidValue(position).into());
+        }
+        while matches!(bytes.get(end), Some(b'0'..=b'9')) {
+            end += 1;
+        }
+    }
+
+    if matches!(bytes.get(end), Some(b'e' | b'E')) {
+        is_float = true;
+        end += 1;
+        if matches!(bytes.get(end), Some(b'+' | b'-')) {
+            end += 1;
+        }
+        if !matches!(bytes.get(end), Some(b'0'..=b'9')) {
+            return Err(ParserError::InvalidValue(position).into());
+        }
+        while matches!(bytes.get(end), Some(b'0'..=b'9')) {
+            end += 1;
+        }
+    }
+
+    let slice = &content[start..end];
+    //Whole numbers stay integers so they survive a round trip; values above
+    //`i64::MAX` fall back to `u64`, and anything still out of range (or with a
+    //fraction/exponent) is kept as an `f64`.
+    let number = if is_float {
+        JsonNumber::Float(slice.parse::<f64>()?)
+    } else if let Ok(value) = slice.parse::<i64>() {
+        JsonNumber::Integer(value)
+    } else if let Ok(value) = slice.parse::<u64>() {
+        JsonNumber::Unsigned(value)
+    } else {
+        JsonNumber::Float(slice.parse::<f64>()?)
+    };
+
+    Ok((JsonTokens::Number(number), end - start))
+}
+
+/// The tokenizer. It owns the read position and the line/column counters and
+/// hands back one token at a time from `next_token`, so tokenization is kept
+/// separate from whatever drives it (`scan_json` loops over it to build the
+/// token stream).
+struct Lexer<'a> {
+    content: &'a str,
+    current: usize,
+    line: usize,
+    column: usize,
+}
+
+impl<'a> Lexer<'a> {
+    fn new(content: &'a str) -> Self {
+        Self {
+            content,
+            current: 0,
+            line: 1,
+            column: 1,
+        }
+    }
+
+    /// Produce the next token in the input. Once the source is exhausted this
+    /// keeps returning `Eof`, so a caller can loop until it sees that variant.
+    fn next_token(&mut self) -> Result<Token<'a>> {
+        let bytes = self.content.as_bytes();
+
+        while self.current < bytes.len() {
+            if bytes[self.current].is_ascii_whitespace() {
+                if bytes[self.current] == b'\n' {
+                    self.line += 1;
+                    self.column = 1;
+                } else {
+                    self.column += 1;
+                }
+                self.current += 1;
                 continue;
             }
 
-            unknown => panic!("The lexer hit a unknown symbol please add {}", unknown),
+            let position = Position {
+                offset: self.current,
+                line: self.line,
+                column: self.column,
+            };
+
+            //A string or number may span several bytes, so we let the scanner
+            //tell us how far it consumed and advance the column by the same
+            //amount; raw newlines never appear inside those tokens.
+            let kind = match bytes[self.current] {
+                b'{' => JsonTokens::OpenCurlyBrace,
+                b'}' => JsonTokens::ClosingCurlyBrace,
+                b'[' => JsonTokens::OpenSquareBrace,
+                b']' => JsonTokens::ClosingSquareBrace,
+                b':' => JsonTokens::Colon,
+                b',' => JsonTokens::Comma,
+                b'"' => {
+                    let (kind, length) = scan_string(self.content, self.current, position)?;
+                    self.current += length;
+                    self.column += length;
+                    return Ok(Token { kind, position });
+                }
+                b'-' | b'0'..=b'9' => {
+                    let (kind, length) = scan_number(self.content, self.current, position)?;
+                    self.current += length;
+                    self.column += length;
+                    return Ok(Token { kind, position });
+                }
+                b'_' | b'a'..=b'z' | b'A'..=b'Z' => {
+                    let (kind, length) = scan_identifier(self.content, self.current)?;
+                    self.current += length;
+                    self.column += length;
+                    return Ok(Token { kind, position });
+                }
+
+                //Any other lead byte (a stray symbol or non-ASCII byte outside a
+                //string) is not the start of a token, so report where it is
+                //instead of panicking on piped input.
+                _ => return Err(ParserError::InvalidValue(position).into()),
+            };
+
+            self.current += 1;
+            self.column += 1;
+            return Ok(Token { kind, position });
         }
 
-        current += 1;
+        Ok(Token {
+            kind: JsonTokens::Eof,
+            position: Position {
+                offset: self.current,
+                line: self.line,
+                column: self.column,
+            },
+        })
+    }
+}
+
+fn scan_json(content: &str) -> Result<Vec<Token<'_>>> {
+    let mut lexer = Lexer::new(content);
+    let mut tokens = vec![];
+
+    loop {
+        let token = lexer.next_token()?;
+        let is_eof = token.kind == JsonTokens::Eof;
+        tokens.push(token);
+        if is_eof {
+            break;
+        }
     }
 
-    tokens.push(JsonTokens::Eof);
-    dbg!(&tokens);
     Ok(tokens)
 }
 
-//TODO: These should take more information i.e. where did the error occured,
-//      what exactly did ruffle the parses feathers the wrong way ?
-//TODO: Name these Error Types better
 #[derive(Debug, Clone)]
 enum ParserError {
-    InvalidSymbolInCurrentContext,
-    InvalidKey,
-    MissingSymbol,
-    InvalidValueInCurrentContext,
+    /// Expected a `:` separating an object key from its value.
+    ExpectedColon(Position),
+    /// The root value was fully parsed but more tokens followed it.
+    TrailingCharacter(Position),
+    /// A token was found where the grammar expected something else.
+    UnexpectedToken {
+        position: Position,
+        found: String,
+        expected: String,
+    },
+    /// An object key was not a quoted string.
+    InvalidKey(Position),
+    /// A string's closing quote was missing.
+    MissingSymbol(Position),
+    /// A token could not be read as a valid JSON value.
+    InvalidValue(Position),
+    /// Containers were nested deeper than the configured maximum depth.
+    MaxDepthExceeded(Position),
+    /// The input contained no tokens at all.
     EmptyJson,
-    InvalidValue,
 }
 
 impl std::fmt::Display for ParserError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            Self::InvalidSymbolInCurrentContext => write!(f, "todo"),
-            Self::InvalidKey => write!(f, "todo"),
-            Self::MissingSymbol => write!(f, "todo"),
-            Self::InvalidValueInCurrentContext => write!(f, "todo"),
-            Self::EmptyJson => write!(f, "todo"),
-            Self::InvalidValue => write!(f, "todo"),
+            Self::ExpectedColon(pos) => write!(
+                f,
+                "line {}, column {}: expected ':' after object key",
+                pos.line, pos.column
+            ),
+            Self::TrailingCharacter(pos) => write!(
+                f,
+                "line {}, column {}: trailing characters after the JSON value",
+                pos.line, pos.column
+            ),
+            Self::UnexpectedToken {
+                position,
+                found,
+                expected,
+            } => write!(
+                f,
+                "line {}, column {}: expected {}, found {}",
+                position.line, position.column, expected, found
+            ),
+            Self::InvalidKey(pos) => write!(
+                f,
+                "line {}, column {}: expected a string key",
+                pos.line, pos.column
+            ),
+            Self::MissingSymbol(pos) => write!(
+                f,
+                "line {}, column {}: missing a closing symbol",
+                pos.line, pos.column
+            ),
+            Self::InvalidValue(pos) => write!(
+                f,
+                "line {}, column {}: not a valid JSON value",
+                pos.line, pos.column
+            ),
+            Self::MaxDepthExceeded(pos) => write!(
+                f,
+                "line {}, column {}: nesting is deeper than the maximum allowed depth",
+                pos.line, pos.column
+            ),
+            Self::EmptyJson => write!(f, "the input is empty, which is not valid JSON"),
         }
     }
 }
@@ -139,169 +453,453 @@ impl std::error::Error for ParserError {
     }
 }
 
+/// Opt-in relaxations of the strict RFC 8259 grammar.
+///
+/// The default keeps the parser strict; each builder method turns on a single
+/// non-strict behavior so callers can relax exactly what a given document needs.
+#[derive(Debug, Clone)]
+pub struct ParserConfig {
+    /// Accept a single `,` immediately before a closing `}` or `]`.
+    allow_trailing_commas: bool,
+    /// Allow the document's root to be any JSON value (a bare scalar such as
+    /// `42` or `"hi"`, or an array) rather than requiring an object.
+    allow_top_level_scalar: bool,
+    /// When set, `null` values are materialized as this sentinel instead of
+    /// `Json::Null`.
+    map_null: Option<Json>,
+    /// The deepest container nesting the parser will accept before bailing out
+    /// with `MaxDepthExceeded`, so that a resulting value can never overflow the
+    /// stack when it is dropped or formatted.
+    max_depth: usize,
+}
+
+/// The maximum nesting depth accepted unless the caller raises it, matching the
+/// limit `serde_json` applies by default.
+const DEFAULT_MAX_DEPTH: usize = 128;
+
+impl Default for ParserConfig {
+    fn default() -> Self {
+        Self {
+            allow_trailing_commas: false,
+            allow_top_level_scalar: false,
+            map_null: None,
+            max_depth: DEFAULT_MAX_DEPTH,
+        }
+    }
+}
+
+impl ParserConfig {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn max_depth(mut self, depth: usize) -> Self {
+        self.max_depth = depth;
+        self
+    }
+
+    pub fn allow_trailing_commas(mut self, allow: bool) -> Self {
+        self.allow_trailing_commas = allow;
+        self
+    }
+
+    pub fn allow_top_level_scalar(mut self, allow: bool) -> Self {
+        self.allow_top_level_scalar = allow;
+        self
+    }
+
+    pub fn map_null(mut self, sentinel: Json) -> Self {
+        self.map_null = Some(sentinel);
+        self
+    }
+}
+
+/// A container the parser is still in the middle of building.
+///
+/// The parser keeps a stack of these instead of recursing, so nesting depth is
+/// limited by `ParserConfig::max_depth` rather than by the call stack. An
+/// object frame also remembers the key it is waiting to attach a value to.
+enum ParseFrame {
+    Array(Vec<Json>),
+    Object(BTreeMap<String, Json>, Option<String>),
+}
+
 struct Parser<'a> {
     position: usize,
-    tokens: &'a [JsonTokens<'a>],
+    tokens: &'a [Token<'a>],
+    config: ParserConfig,
 }
 
 impl<'a> Parser<'a> {
-    fn new(tokens: &'a [JsonTokens]) -> Self {
+    fn new(tokens: &'a [Token]) -> Self {
+        Self::with_config(tokens, ParserConfig::default())
+    }
+
+    fn with_config(tokens: &'a [Token], config: ParserConfig) -> Self {
         Self {
             position: 0,
             tokens,
+            config,
         }
     }
 
-    fn match_token(&mut self, to_match: JsonTokens) -> bool {
-        if self.tokens[self.position] == to_match {
-            return true;
-        }
-
-        false
+    fn match_token(&mut self, to_match: JsonTokens<'a>) -> bool {
+        self.tokens[self.position].kind == to_match
     }
 
-    fn peek(&mut self) -> Option<&JsonTokens> {
-        Some(&self.tokens[self.position])
+    fn peek(&self) -> Option<&JsonTokens<'a>> {
+        Some(&self.tokens[self.position].kind)
     }
 
-    fn advance(&mut self) -> Option<&JsonTokens> {
-        let next_token = &self.tokens[self.position];
+    fn advance(&mut self) -> Option<&JsonTokens<'_>> {
+        let next_token = &self.tokens[self.position].kind;
         self.position += 1;
 
         Some(next_token)
     }
 
-    fn parse_json_value(&mut self) -> Result<()> {
-        match self.advance() {
-            Some(JsonTokens::String(value, _)) => println!("String: {}", value),
-            Some(JsonTokens::Boolean(value)) => println!("Boolean: {}", value),
-            Some(JsonTokens::Null) => println!("Null"),
-            Some(JsonTokens::OpenCurlyBrace) => self.parse_json_object()?,
-            _ => return Err(ParserError::InvalidValue.into()),
-        }
+    /// The source position of the token the parser is currently looking at.
+    fn current_position(&self) -> Position {
+        self.tokens[self.position].position
+    }
 
-        Ok(())
+    /// A description of the current token for use in error messages.
+    fn describe_current(&self) -> String {
+        self.tokens[self.position].kind.describe()
     }
-    fn parse_json_array(&mut self) -> Result<()> {
-        dbg!(&self.tokens[self.position]);
-        loop {
-            if self.match_token(JsonTokens::ClosingSquareBrace) {
-                self.advance();
-                break;
-            }
 
-            self.parse_json_value()?;
+    /// Advance past the current token and hand back its kind by value, so the
+    /// caller can keep owning a `String`/`Number` payload without fighting the
+    /// borrow checker over the token slice.
+    fn advance_kind(&mut self) -> JsonTokens<'a> {
+        let kind = self.tokens[self.position].kind.clone();
+        self.position += 1;
+        kind
+    }
 
-            if self.match_token(JsonTokens::Comma) {
-                self.advance();
-            }
+    /// Read an object key and return it. RFC 8259 only allows a quoted string,
+    /// so a bare identifier is always rejected.
+    fn parse_key(&mut self) -> Result<String> {
+        let position = self.current_position();
+        match self.advance_kind() {
+            JsonTokens::String(key) => Ok(key),
+            _ => Err(ParserError::InvalidKey(position).into()),
         }
+    }
 
+    /// Consume the `:` that must follow an object key.
+    fn expect_colon(&mut self) -> Result<()> {
+        if !self.match_token(JsonTokens::Colon) {
+            return Err(ParserError::ExpectedColon(self.current_position()).into());
+        }
+        self.advance();
         Ok(())
     }
 
-    fn parse_key_value_pair(&mut self) -> Result<()> {
-        dbg!(&self.tokens[self.position]);
+    /// Parse a single complete JSON value using an explicit stack of in-progress
+    /// containers rather than recursion, so deeply nested input cannot overflow
+    /// the call stack. The grammar's comma/colon rules are enforced uniformly as
+    /// each value is attached to its parent frame.
+    fn parse_value(&mut self) -> Result<Json> {
+        let mut stack: Vec<ParseFrame> = Vec::new();
+
+        // Each pass through the outer loop reads one complete value. Opening a
+        // non-empty container pushes a frame and `continue`s to read its first
+        // member; a scalar or an immediately-closed container falls through to
+        // the inner loop, which attaches the value to its parent and walks up
+        // the stack as containers close.
         loop {
-            match self.advance() {
-                Some(JsonTokens::Identifier(key, _)) => println!("{}", key),
-                Some(JsonTokens::String(key, _)) => println!("{}", key),
-                _ => return Err(ParserError::InvalidKey.into()),
+            let position = self.current_position();
+            let found = self.describe_current();
+            let mut completed = match self.advance_kind() {
+                JsonTokens::String(value) => Json::String(value),
+                JsonTokens::Boolean(value) => Json::Boolean(value),
+                JsonTokens::Number(JsonNumber::Integer(value)) => Json::I64(value),
+                JsonTokens::Number(JsonNumber::Unsigned(value)) => Json::U64(value),
+                JsonTokens::Number(JsonNumber::Float(value)) => Json::F64(value),
+                JsonTokens::Null => self.config.map_null.clone().unwrap_or(Json::Null),
+                JsonTokens::OpenSquareBrace => {
+                    if stack.len() >= self.config.max_depth {
+                        return Err(ParserError::MaxDepthExceeded(position).into());
+                    }
+                    if self.match_token(JsonTokens::ClosingSquareBrace) {
+                        self.advance();
+                        Json::Array(vec![])
+                    } else {
+                        stack.push(ParseFrame::Array(vec![]));
+                        continue;
+                    }
+                }
+                JsonTokens::OpenCurlyBrace => {
+                    if stack.len() >= self.config.max_depth {
+                        return Err(ParserError::MaxDepthExceeded(position).into());
+                    }
+                    if self.match_token(JsonTokens::ClosingCurlyBrace) {
+                        self.advance();
+                        Json::Object(BTreeMap::new())
+                    } else {
+                        let key = self.parse_key()?;
+                        self.expect_colon()?;
+                        stack.push(ParseFrame::Object(BTreeMap::new(), Some(key)));
+                        continue;
+                    }
+                }
+                _ => {
+                    return Err(ParserError::UnexpectedToken {
+                        position,
+                        found,
+                        expected: "a JSON value".to_string(),
+                    }
+                    .into())
+                }
             };
 
-            if !self.match_token(JsonTokens::Colon) {
-                return Err(ParserError::InvalidSymbolInCurrentContext.into());
+            loop {
+                match stack.last_mut() {
+                    None => return Ok(completed),
+                    Some(ParseFrame::Array(elements)) => elements.push(completed),
+                    Some(ParseFrame::Object(object, pending)) => {
+                        let key = pending
+                            .take()
+                            .expect("object frame is missing its pending key");
+                        object.insert(key, completed);
+                    }
+                }
+
+                let in_array = matches!(stack.last(), Some(ParseFrame::Array(_)));
+                if in_array {
+                    if self.match_token(JsonTokens::Comma) {
+                        self.advance();
+                        //A trailing comma closes the array only when the caller
+                        //opted into that relaxation.
+                        if self.config.allow_trailing_commas
+                            && self.match_token(JsonTokens::ClosingSquareBrace)
+                        {
+                            self.advance();
+                            let ParseFrame::Array(elements) = stack.pop().unwrap() else {
+                                unreachable!()
+                            };
+                            completed = Json::Array(elements);
+                        } else {
+                            break;
+                        }
+                    } else if self.match_token(JsonTokens::ClosingSquareBrace) {
+                        self.advance();
+                        let ParseFrame::Array(elements) = stack.pop().unwrap() else {
+                            unreachable!()
+                        };
+                        completed = Json::Array(elements);
+                    } else {
+                        return Err(ParserError::UnexpectedToken {
+                            position: self.current_position(),
+                            found: self.describe_current(),
+                            expected: "',' or ']'".to_string(),
+                        }
+                        .into());
+                    }
+                } else if self.match_token(JsonTokens::Comma) {
+                    self.advance();
+                    if self.config.allow_trailing_commas
+                        && self.match_token(JsonTokens::ClosingCurlyBrace)
+                    {
+                        self.advance();
+                        let ParseFrame::Object(object, _) = stack.pop().unwrap() else {
+                            unreachable!()
+                        };
+                        completed = Json::Object(object);
+                    } else {
+                        let key = self.parse_key()?;
+                        self.expect_colon()?;
+                        if let Some(ParseFrame::Object(_, pending)) = stack.last_mut() {
+                            *pending = Some(key);
+                        }
+                        break;
+                    }
+                } else if self.match_token(JsonTokens::ClosingCurlyBrace) {
+                    self.advance();
+                    let ParseFrame::Object(object, _) = stack.pop().unwrap() else {
+                        unreachable!()
+                    };
+                    completed = Json::Object(object);
+                } else {
+                    return Err(ParserError::UnexpectedToken {
+                        position: self.current_position(),
+                        found: self.describe_current(),
+                        expected: "',' or '}'".to_string(),
+                    }
+                    .into());
+                }
             }
+        }
+    }
 
-            self.advance();
-
-            match self.advance() {
-                Some(JsonTokens::Identifier(value, _)) => println!("{}", value),
-                Some(JsonTokens::String(value, _)) => println!("{}", value),
-                Some(JsonTokens::Boolean(value)) => println!("{}", value),
-                Some(JsonTokens::Null) => println!("null"),
-                Some(JsonTokens::OpenCurlyBrace) => self.parse_json_object()?,
-                Some(JsonTokens::OpenSquareBrace) => self.parse_json_array()?,
-                _ => return Err(ParserError::InvalidValueInCurrentContext.into()), 
-            };
-
-            if !self.match_token(JsonTokens::Comma) {
-                break;
+    fn parse(&mut self) -> Result<Json> {
+        let position = self.current_position();
+        match self.peek() {
+            Some(JsonTokens::Eof) | None => return Err(ParserError::EmptyJson.into()),
+            Some(JsonTokens::OpenCurlyBrace) => {}
+            _ if self.config.allow_top_level_scalar => {}
+            _ => {
+                return Err(ParserError::UnexpectedToken {
+                    position,
+                    found: self.describe_current(),
+                    expected: "'{'".to_string(),
+                }
+                .into())
             }
+        }
 
-            self.advance();
+        let value = self.parse_value()?;
+
+        if !self.match_token(JsonTokens::Eof) {
+            return Err(ParserError::TrailingCharacter(self.current_position()).into());
         }
 
-        Ok(())
+        Ok(value)
     }
+}
 
-    fn parse_json_object(&mut self) -> Result<()> {
-        //FIXME: Potential to blow the stack if we recurse to deep !
-        //TODO: Collapse these Error, where possible into one branch
-        match self.peek() {
-            Some(JsonTokens::OpenCurlyBrace) => return Err(ParserError::InvalidSymbolInCurrentContext.into()),
-            Some(JsonTokens::ClosingCurlyBrace) => return Ok(()),
-            Some(JsonTokens::OpenSquareBrace) => return Err(ParserError::InvalidSymbolInCurrentContext.into()),
-            Some(JsonTokens::ClosingSquareBrace) => return Err(ParserError::InvalidSymbolInCurrentContext.into()),
-            Some(JsonTokens::Colon) => return Err(ParserError::InvalidSymbolInCurrentContext.into()),
-            Some(JsonTokens::Comma) => return Err(ParserError::InvalidSymbolInCurrentContext.into()),
-            Some(JsonTokens::Identifier(_, _)) => self.parse_key_value_pair()?,
-            Some(JsonTokens::Boolean(_)) => return Err(ParserError::InvalidSymbolInCurrentContext.into()),
-            Some(JsonTokens::Null) => return Err(ParserError::InvalidValueInCurrentContext.into()),
-            Some(JsonTokens::String(_, _)) => self.parse_key_value_pair()?,
-            Some(JsonTokens::Eof) => return Err(ParserError::EmptyJson.into()),
-            None => return Err(ParserError::EmptyJson.into()),
-        };
-
-        if !self.match_token(JsonTokens::ClosingCurlyBrace) {
-            return Err(ParserError::MissingSymbol.into());
+/// Append `string` to `out` as a quoted, escaped JSON string literal.
+fn encode_str(string: &str, out: &mut String) {
+    out.push('"');
+    for ch in string.chars() {
+        match ch {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\u{0008}' => out.push_str("\\b"),
+            '\u{000C}' => out.push_str("\\f"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            //Remaining control characters have no short escape, so spell them
+            //out as a `\uXXXX` sequence.
+            control if (control as u32) < 0x20 => {
+                out.push_str(&format!("\\u{:04x}", control as u32))
+            }
+            other => out.push(other),
         }
-
-        Ok(())
     }
+    out.push('"');
+}
 
-    fn parse(&mut self) -> Result<()> {
-        match self.advance() {
-            Some(JsonTokens::OpenCurlyBrace) => self.parse_json_object()?,
-            Some(JsonTokens::ClosingCurlyBrace) => return Err(ParserError::InvalidSymbolInCurrentContext.into()), 
-            Some(JsonTokens::OpenSquareBrace) => return Err(ParserError::InvalidSymbolInCurrentContext.into()),
-            Some(JsonTokens::ClosingSquareBrace) => return Err(ParserError::InvalidSymbolInCurrentContext.into()),
-            Some(JsonTokens::Colon) => return Err(ParserError::InvalidSymbolInCurrentContext.into()),
-            Some(JsonTokens::Comma) => return Err(ParserError::InvalidSymbolInCurrentContext.into()),
-            Some(JsonTokens::Identifier(_, _)) => return Err(ParserError::InvalidValueInCurrentContext.into()),
-            Some(JsonTokens::Boolean(_)) => return Err(ParserError::InvalidValueInCurrentContext.into()),
-            Some(JsonTokens::Null) => return Err(ParserError::InvalidValueInCurrentContext.into()),
-            Some(JsonTokens::String(_, _)) => return Err(ParserError::InvalidValueInCurrentContext.into()),
-            Some(JsonTokens::Eof) => return Err(ParserError::InvalidValueInCurrentContext.into()),
-            None => todo!(),
-        };
+/// Push `count` spaces of indentation onto `out`.
+fn encode_indent(out: &mut String, count: usize) {
+    for _ in 0..count {
+        out.push(' ');
+    }
+}
 
-        Ok(())
+/// Write `value` onto `out` without any insignificant whitespace.
+fn encode_compact(value: &Json, out: &mut String) {
+    match value {
+        Json::Null => out.push_str("null"),
+        Json::Boolean(boolean) => out.push_str(if *boolean { "true" } else { "false" }),
+        Json::I64(number) => out.push_str(&number.to_string()),
+        Json::U64(number) => out.push_str(&number.to_string()),
+        //`Debug` keeps a `.0` or exponent on whole floats, so they parse back
+        //as `F64` rather than as an integer.
+        Json::F64(number) => out.push_str(&format!("{:?}", number)),
+        Json::String(string) => encode_str(string, out),
+        Json::Array(elements) => {
+            out.push('[');
+            for (index, element) in elements.iter().enumerate() {
+                if index > 0 {
+                    out.push(',');
+                }
+                encode_compact(element, out);
+            }
+            out.push(']');
+        }
+        Json::Object(object) => {
+            out.push('{');
+            for (index, (key, member)) in object.iter().enumerate() {
+                if index > 0 {
+                    out.push(',');
+                }
+                encode_str(key, out);
+                out.push(':');
+                encode_compact(member, out);
+            }
+            out.push('}');
+        }
     }
 }
 
-fn main() -> Result<()> {
-    let file_content = fs::read_to_string("examples/test.json")?;
+/// Write `value` onto `out`, newlining and indenting nested containers by
+/// `indent` spaces per level. `depth` is the nesting level of `value` itself.
+fn encode_pretty(value: &Json, indent: usize, depth: usize, out: &mut String) {
+    match value {
+        Json::Array(elements) if !elements.is_empty() => {
+            out.push('[');
+            for (index, element) in elements.iter().enumerate() {
+                if index > 0 {
+                    out.push(',');
+                }
+                out.push('\n');
+                encode_indent(out, indent * (depth + 1));
+                encode_pretty(element, indent, depth + 1, out);
+            }
+            out.push('\n');
+            encode_indent(out, indent * depth);
+            out.push(']');
+        }
+        Json::Object(object) if !object.is_empty() => {
+            out.push('{');
+            for (index, (key, member)) in object.iter().enumerate() {
+                if index > 0 {
+                    out.push(',');
+                }
+                out.push('\n');
+                encode_indent(out, indent * (depth + 1));
+                encode_str(key, out);
+                out.push_str(": ");
+                encode_pretty(member, indent, depth + 1, out);
+            }
+            out.push('\n');
+            encode_indent(out, indent * depth);
+            out.push('}');
+        }
+        //Scalars and empty containers have nothing to indent.
+        scalar => encode_compact(scalar, out),
+    }
+}
 
-    let mut stdout = io::stdout();
-    let _ = stdout.lock();
+/// Render a `Json` value back to a compact JSON string, the inverse of `parse`.
+pub fn to_string(value: &Json) -> String {
+    let mut out = String::new();
+    encode_compact(value, &mut out);
+    out
+}
 
-    //TODO: For now
-    let lexed_json = scan_json(&file_content)?;
-    //parse_json(&lexed_json);
+/// Render a `Json` value to an indented, human readable JSON string using
+/// `indent` spaces per nesting level.
+pub fn to_string_pretty(value: &Json, indent: usize) -> String {
+    let mut out = String::new();
+    encode_pretty(value, indent, 0, &mut out);
+    out
+}
 
-    let mut parser = Parser::new(&lexed_json);
-    parser.parse()?;
+/// Parse JSON from any buffered reader, so a file or piped stdin can be handed
+/// to the same front end. The reader is drained into a `String` before lexing;
+/// incremental, bounded-memory streaming is not yet implemented.
+fn parse_reader<R: BufRead>(mut reader: R) -> Result<Json> {
+    let mut content = String::new();
+    reader.read_to_string(&mut content)?;
 
-    if lexed_json.first() == Some(&JsonTokens::Eof) {
-        let mut stderr = io::stderr();
-        let _ = stderr.lock();
+    let tokens = scan_json(&content)?;
+    Parser::new(&tokens).parse()
+}
 
-        write!(stderr, "Invalid Json file")?;
-        process::exit(1);
-    }
+fn main() -> Result<()> {
+    //Read from the file named on the command line, or fall back to stdin so the
+    //document can be piped in.
+    let json = match std::env::args().nth(1) {
+        Some(path) => parse_reader(io::BufReader::new(fs::File::open(path)?))?,
+        None => parse_reader(io::stdin().lock())?,
+    };
 
-    write!(stdout, "{}", &file_content)?;
+    let mut stdout = io::stdout();
+    writeln!(stdout, "{:?}", json)?;
 
     Ok(())
 }
@@ -450,4 +1048,67 @@ mod test {
 
         assert_eq!(parser.is_ok(), true);
     }
+
+    #[test]
+    fn test_to_string_escapes_and_nests() {
+        //A compact render quotes keys, escapes control characters and places no
+        //insignificant whitespace between tokens.
+        let mut object = BTreeMap::new();
+        object.insert("a".to_string(), Json::I64(1));
+        object.insert(
+            "b".to_string(),
+            Json::Array(vec![Json::String("x\"y\n".to_string()), Json::Null]),
+        );
+
+        assert_eq!(
+            to_string(&Json::Object(object)),
+            "{\"a\":1,\"b\":[\"x\\\"y\\n\",null]}"
+        );
+    }
+
+    #[test]
+    fn test_to_string_pretty_indents_nested_containers() {
+        let mut object = BTreeMap::new();
+        object.insert("a".to_string(), Json::Array(vec![Json::I64(1)]));
+
+        assert_eq!(
+            to_string_pretty(&Json::Object(object), 2),
+            "{\n  \"a\": [\n    1\n  ]\n}"
+        );
+    }
+
+    #[test]
+    fn test_trailing_comma_is_rejected_by_default_but_allowed_when_configured() {
+        let tokens = scan_json("{\"a\": 1,}").unwrap();
+
+        assert!(Parser::new(&tokens).parse().is_err());
+
+        let config = ParserConfig::new().allow_trailing_commas(true);
+        assert!(Parser::with_config(&tokens, config).parse().is_ok());
+    }
+
+    #[test]
+    fn test_top_level_scalar_requires_opting_in() {
+        let tokens = scan_json("42").unwrap();
+
+        assert!(Parser::new(&tokens).parse().is_err());
+
+        let config = ParserConfig::new().allow_top_level_scalar(true);
+        assert_eq!(
+            Parser::with_config(&tokens, config).parse().unwrap(),
+            Json::I64(42)
+        );
+    }
+
+    #[test]
+    fn test_map_null_substitutes_the_chosen_sentinel() {
+        let tokens = scan_json("{\"a\": null}").unwrap();
+
+        let config = ParserConfig::new().map_null(Json::String("<nil>".to_string()));
+        let parsed = Parser::with_config(&tokens, config).parse().unwrap();
+
+        let mut expected = BTreeMap::new();
+        expected.insert("a".to_string(), Json::String("<nil>".to_string()));
+        assert_eq!(parsed, Json::Object(expected));
+    }
 }